@@ -0,0 +1,192 @@
+use std::{
+    io::{BufWriter, Write},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::AppState;
+
+/// Render-specific details a handler attaches to the response so the access
+/// log middleware can report them without re-deriving anything.
+#[derive(Debug, Clone)]
+pub struct RenderFields {
+    pub pdf_bytes: u64,
+    pub pages_rendered: Option<usize>,
+    pub blake3_id: String,
+}
+
+pub fn attach(response: &mut Response, fields: RenderFields) {
+    response.extensions_mut().insert(fields);
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct LoggedQuery {
+    format: Option<String>,
+    pages: Option<String>,
+    scale: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct AccessLogEntry {
+    timestamp_ms: u64,
+    client: String,
+    method: String,
+    path: String,
+    query: LoggedQuery,
+    status: u16,
+    pdf_bytes: Option<u64>,
+    pages_rendered: Option<usize>,
+    duration_ms: u128,
+    blake3_id: Option<String>,
+}
+
+/// A background job's actual render-and-upload outcome, logged from
+/// `queue::run_job` once the job finishes, independent of the enqueueing
+/// request's own access log line.
+#[derive(Debug, Serialize)]
+struct JobCompletionEntry {
+    timestamp_ms: u64,
+    job_id: String,
+    blake3_id: String,
+    status: &'static str,
+    pages_rendered: Option<usize>,
+    error: Option<String>,
+    duration_ms: u128,
+}
+
+/// A buffered sink for structured per-request access log lines, appended to
+/// the single file at `PDF_ACCESS_LOG_PATH` and flushed once a second. The
+/// file is not rotated.
+///
+/// When `PDF_ACCESS_LOG_PATH` isn't configured, entries fall back to the
+/// existing `tracing` output instead.
+#[derive(Clone)]
+pub struct AccessLog {
+    writer: Option<Arc<Mutex<BufWriter<std::fs::File>>>>,
+}
+
+impl AccessLog {
+    pub fn new(path: Option<&str>) -> anyhow::Result<Self> {
+        let writer = path
+            .map(|path| {
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map(|file| Arc::new(Mutex::new(BufWriter::new(file))))
+            })
+            .transpose()?;
+
+        if let Some(writer) = &writer {
+            spawn_flusher(writer.clone());
+        }
+
+        Ok(Self { writer })
+    }
+
+    fn record(&self, entry: &AccessLogEntry) {
+        self.emit(entry);
+    }
+
+    /// Records a background job's actual completion, separate from the HTTP
+    /// request/response cycle: for `?mode=async` uploads the handler returns
+    /// as soon as the job is enqueued, long before rendering finishes, so the
+    /// request-scoped `middleware` below can't report real duration or
+    /// `pages_rendered` for it.
+    pub fn record_job_completion(
+        &self,
+        job_id: &str,
+        blake3_id: &str,
+        pages_rendered: Option<usize>,
+        error: Option<&str>,
+        duration: Duration,
+    ) {
+        self.emit(&JobCompletionEntry {
+            timestamp_ms: now_ms(),
+            job_id: job_id.to_owned(),
+            blake3_id: blake3_id.to_owned(),
+            status: if error.is_some() { "failed" } else { "completed" },
+            pages_rendered,
+            error: error.map(str::to_owned),
+            duration_ms: duration.as_millis(),
+        });
+    }
+
+    fn emit(&self, entry: &impl Serialize) {
+        let Ok(line) = serde_json::to_string(entry) else {
+            return;
+        };
+
+        match &self.writer {
+            Some(writer) => {
+                if let Ok(mut writer) = writer.lock() {
+                    let _ = writeln!(writer, "{line}");
+                }
+            }
+            None => tracing::info!(target: "access_log", "{line}"),
+        }
+    }
+}
+
+fn spawn_flusher(writer: Arc<Mutex<BufWriter<std::fs::File>>>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+
+        loop {
+            interval.tick().await;
+            if let Ok(mut writer) = writer.lock() {
+                let _ = writer.flush();
+            }
+        }
+    });
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub async fn middleware(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let start = Instant::now();
+    let method = req.method().to_string();
+    let path = req.uri().path().to_owned();
+    let query = req
+        .uri()
+        .query()
+        .and_then(|q| serde_urlencoded::from_str(q).ok())
+        .unwrap_or_default();
+
+    let mut response = next.run(req).await;
+
+    let fields = response.extensions_mut().remove::<RenderFields>();
+
+    state.access_log.record(&AccessLogEntry {
+        timestamp_ms: now_ms(),
+        client: addr.to_string(),
+        method,
+        path,
+        query,
+        status: response.status().as_u16(),
+        pdf_bytes: fields.as_ref().map(|f| f.pdf_bytes),
+        pages_rendered: fields.as_ref().and_then(|f| f.pages_rendered),
+        duration_ms: start.elapsed().as_millis(),
+        blake3_id: fields.map(|f| f.blake3_id),
+    });
+
+    response
+}
@@ -0,0 +1,175 @@
+//! A from-scratch BlurHash encoder over a rendered [`DynamicImage`], following
+//! the reference algorithm at <https://github.com/woltapp/blurhash>.
+
+use image::{DynamicImage, GenericImageView};
+
+const BASE83_CHARS: &[u8] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+/// Encodes `image` as a BlurHash string using `components_x` by `components_y`
+/// basis functions (each clamped to `1..=9`, per the format's size flag).
+pub fn encode(image: &DynamicImage, components_x: u32, components_y: u32) -> String {
+    let components_x = components_x.clamp(1, 9);
+    let components_y = components_y.clamp(1, 9);
+
+    let (width, height) = image.dimensions();
+    let rgb = image.to_rgb8();
+
+    let factors: Vec<(f64, f64, f64)> = (0..components_y)
+        .flat_map(|cy| (0..components_x).map(move |cx| (cx, cy)))
+        .map(|(cx, cy)| basis_factor(&rgb, width, height, cx, cy))
+        .collect();
+
+    let dc = factors[0];
+    let ac = &factors[1..];
+
+    let mut hash = String::new();
+
+    let size_flag = (components_x - 1) + (components_y - 1) * 9;
+    hash.push_str(&base83_encode(size_flag, 1));
+
+    let max_value = if ac.is_empty() {
+        hash.push_str(&base83_encode(0, 1));
+        1.0
+    } else {
+        let actual_max = ac
+            .iter()
+            .flat_map(|&(r, g, b)| [r.abs(), g.abs(), b.abs()])
+            .fold(0.0_f64, f64::max);
+
+        let quantized_max = (actual_max * 166.0 - 0.5).floor().clamp(0.0, 82.0) as u32;
+        hash.push_str(&base83_encode(quantized_max, 1));
+
+        (quantized_max + 1) as f64 / 166.0
+    };
+
+    hash.push_str(&base83_encode(encode_dc(dc), 4));
+
+    for &factor in ac {
+        hash.push_str(&base83_encode(encode_ac(factor, max_value), 2));
+    }
+
+    hash
+}
+
+/// Computes `normalization * Σ color · cos(π·cx·x/W) · cos(π·cy·y/H)` for one
+/// `(cx, cy)` basis pair, over the image converted to linear light.
+fn basis_factor(
+    rgb: &image::RgbImage,
+    width: u32,
+    height: u32,
+    cx: u32,
+    cy: u32,
+) -> (f64, f64, f64) {
+    let mut r = 0.0;
+    let mut g = 0.0;
+    let mut b = 0.0;
+
+    for y in 0..height {
+        let cos_y = (std::f64::consts::PI * cy as f64 * y as f64 / height as f64).cos();
+
+        for x in 0..width {
+            let cos_x = (std::f64::consts::PI * cx as f64 * x as f64 / width as f64).cos();
+            let basis = cos_x * cos_y;
+
+            let pixel = rgb.get_pixel(x, y);
+            r += basis * srgb_to_linear(pixel[0]);
+            g += basis * srgb_to_linear(pixel[1]);
+            b += basis * srgb_to_linear(pixel[2]);
+        }
+    }
+
+    let normalization = if cx == 0 && cy == 0 { 1.0 } else { 2.0 };
+    let scale = normalization / (width as f64 * height as f64);
+
+    (r * scale, g * scale, b * scale)
+}
+
+fn srgb_to_linear(value: u8) -> f64 {
+    let c = value as f64 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(value: f64) -> u32 {
+    let v = value.clamp(0.0, 1.0);
+    let c = if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+
+    (c * 255.0).round().clamp(0.0, 255.0) as u32
+}
+
+fn encode_dc(color: (f64, f64, f64)) -> u32 {
+    let (r, g, b) = color;
+    (linear_to_srgb(r) << 16) + (linear_to_srgb(g) << 8) + linear_to_srgb(b)
+}
+
+fn encode_ac(color: (f64, f64, f64), max_value: f64) -> u32 {
+    let quantize = |c: f64| -> u32 {
+        let normalized = c / max_value;
+        (normalized.signum() * normalized.abs().powf(0.5) * 9.0 + 9.5)
+            .clamp(0.0, 18.0) as u32
+    };
+
+    let (r, g, b) = color;
+    quantize(r) * 19 * 19 + quantize(g) * 19 + quantize(b)
+}
+
+fn base83_encode(mut value: u32, length: usize) -> String {
+    let mut bytes = vec![0u8; length];
+    for slot in bytes.iter_mut().rev() {
+        *slot = BASE83_CHARS[(value % 83) as usize];
+        value /= 83;
+    }
+
+    String::from_utf8(bytes).expect("base83 alphabet is ASCII")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    fn solid_image(width: u32, height: u32, pixel: [u8; 3]) -> DynamicImage {
+        let mut image = RgbImage::new(width, height);
+        for p in image.pixels_mut() {
+            *p = image::Rgb(pixel);
+        }
+
+        DynamicImage::ImageRgb8(image)
+    }
+
+    #[test]
+    fn encodes_to_the_expected_length_for_default_components() {
+        let image = solid_image(32, 32, [128, 64, 200]);
+        let hash = encode(&image, 4, 3);
+
+        // 1 size flag + 1 max-AC flag + 4 DC chars + 2 chars per remaining AC term.
+        assert_eq!(hash.len(), 1 + 1 + 4 + 2 * (4 * 3 - 1));
+    }
+
+    #[test]
+    fn clamps_components_to_the_1_to_9_range() {
+        let image = solid_image(8, 8, [10, 10, 10]);
+        let hash = encode(&image, 20, 0);
+
+        // components clamp to 9x1, so 8 AC terms remain.
+        assert_eq!(hash.len(), 1 + 1 + 4 + 2 * 8);
+    }
+
+    #[test]
+    fn srgb_linear_roundtrip_is_lossless_within_rounding() {
+        for value in [0u8, 1, 16, 64, 128, 200, 255] {
+            let roundtripped = linear_to_srgb(srgb_to_linear(value));
+            assert!((roundtripped as i32 - value as i32).abs() <= 1);
+        }
+    }
+
+    #[test]
+    fn base83_encode_pads_to_a_fixed_width() {
+        assert_eq!(base83_encode(0, 4), "0000");
+        assert_eq!(base83_encode(82, 1), "~");
+    }
+}
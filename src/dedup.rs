@@ -0,0 +1,181 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex, Weak},
+};
+
+use tokio::sync::OnceCell;
+
+use crate::{AppError, OutputFormat, UploadQuery, UploadedImage};
+
+/// Identifies a render request independent of which client sent it: two
+/// uploads with the same PDF bytes and the same output-affecting query
+/// parameters produce identical output and can share a single render.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Fingerprint {
+    hash: String,
+    format: OutputFormat,
+    scale_bits: Option<u32>,
+    pages: Option<String>,
+    blurhash: bool,
+    resize: Option<String>,
+    thumbnail: Option<u32>,
+    crop: Option<String>,
+}
+
+impl Fingerprint {
+    pub fn new(hash: &str, query: &UploadQuery) -> Self {
+        Self {
+            hash: hash.to_owned(),
+            format: query.format,
+            scale_bits: query.scale.map(f32::to_bits),
+            pages: query.pages.clone(),
+            blurhash: query.blurhash,
+            resize: query.resize.clone(),
+            thumbnail: query.thumbnail,
+            crop: query.crop.clone(),
+        }
+    }
+}
+
+type SharedResult = Result<Vec<UploadedImage>, String>;
+
+struct Shared {
+    result: OnceCell<SharedResult>,
+}
+
+/// Coalesces concurrent renders that share a [`Fingerprint`].
+///
+/// The first request for a fingerprint performs the work; any request that
+/// arrives while that work is in flight awaits the same result instead of
+/// re-rendering and re-uploading. Entries are held by `Weak` reference, so
+/// once every in-flight waiter has dropped its handle the fingerprint is
+/// forgotten and the next request (success or failure) starts fresh.
+#[derive(Clone, Default)]
+pub struct ConcurrentProcessor {
+    inflight: Arc<Mutex<HashMap<Fingerprint, Weak<Shared>>>>,
+}
+
+impl ConcurrentProcessor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn dedup<F>(
+        &self,
+        fingerprint: Fingerprint,
+        work: F,
+    ) -> Result<Vec<UploadedImage>, AppError>
+    where
+        F: Future<Output = Result<Vec<UploadedImage>, AppError>>,
+    {
+        let shared = {
+            let mut inflight = self.inflight.lock().unwrap();
+
+            match inflight.get(&fingerprint).and_then(Weak::upgrade) {
+                Some(shared) => shared,
+                None => {
+                    let shared = Arc::new(Shared {
+                        result: OnceCell::new(),
+                    });
+                    inflight.insert(fingerprint.clone(), Arc::downgrade(&shared));
+                    shared
+                }
+            }
+        };
+
+        let result = shared
+            .result
+            .get_or_init(|| async move { work.await.map_err(|err| err.to_string()) })
+            .await
+            .clone();
+
+        // Release this waiter's handle first, so the last waiter out prunes
+        // its own entry.
+        drop(shared);
+
+        self.inflight
+            .lock()
+            .unwrap()
+            .retain(|_, weak| weak.strong_count() > 0);
+
+        result.map_err(AppError::Render)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    fn fingerprint(hash: &str) -> Fingerprint {
+        Fingerprint::new(
+            hash,
+            &UploadQuery {
+                format: OutputFormat::default(),
+                token: None,
+                pages: None,
+                scale: None,
+                mode: Default::default(),
+                blurhash: false,
+                resize: None,
+                thumbnail: None,
+                crop: None,
+                metadata_only: false,
+            },
+        )
+    }
+
+    fn image(name: &str) -> UploadedImage {
+        UploadedImage { name: name.to_owned(), blurhash: None }
+    }
+
+    #[tokio::test]
+    async fn concurrent_dedup_calls_share_a_single_render() {
+        let processor = ConcurrentProcessor::new();
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let work = |runs: Arc<AtomicUsize>| async move {
+            runs.fetch_add(1, Ordering::SeqCst);
+            tokio::task::yield_now().await;
+            Ok(vec![image("a.png")])
+        };
+
+        let (first, second) = tokio::join!(
+            processor.dedup(fingerprint("same"), work(runs.clone())),
+            processor.dedup(fingerprint("same"), work(runs.clone())),
+        );
+
+        assert_eq!(runs.load(Ordering::SeqCst), 1, "work must run exactly once");
+        assert_eq!(first.unwrap()[0].name, second.unwrap()[0].name);
+    }
+
+    #[tokio::test]
+    async fn distinct_fingerprints_each_run_their_own_work() {
+        let processor = ConcurrentProcessor::new();
+
+        let first = processor.dedup(fingerprint("a"), async { Ok(vec![image("a.png")]) }).await;
+        let second = processor.dedup(fingerprint("b"), async { Ok(vec![image("b.png")]) }).await;
+
+        assert_eq!(first.unwrap()[0].name, "a.png");
+        assert_eq!(second.unwrap()[0].name, "b.png");
+    }
+
+    #[tokio::test]
+    async fn a_later_submission_retries_after_every_waiter_drops_a_failure() {
+        let processor = ConcurrentProcessor::new();
+
+        let failed = processor
+            .dedup(fingerprint("retry"), async { Err(AppError::InvalidOperation("boom".to_owned())) })
+            .await;
+        assert!(failed.is_err());
+
+        // Once the failed call above returns, its `Arc<Shared>` is dropped and
+        // the `inflight` entry for this fingerprint is pruned, so a fresh
+        // submission must re-run the work rather than replaying the cached
+        // error forever.
+        let succeeded = processor.dedup(fingerprint("retry"), async { Ok(vec![image("retry.png")]) }).await;
+        assert!(succeeded.is_ok());
+    }
+}
@@ -0,0 +1,89 @@
+use pdfium_render::prelude::{PdfDocument, PdfDocumentMetadataTagType, PdfPageRenderRotation};
+use serde::Serialize;
+
+use crate::{bind_pdfium, AppError, OutputFormat, UploadQuery};
+
+#[derive(Debug, Serialize)]
+pub struct DocumentDetails {
+    pub blake3_id: String,
+    pub format: &'static str,
+    pub mime_type: &'static str,
+    pub page_count: usize,
+    pub pages: Vec<PageDetails>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub created_at: Option<String>,
+    pub modified_at: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PageDetails {
+    pub index: usize,
+    pub width: u32,
+    pub height: u32,
+    pub rotation_degrees: u32,
+}
+
+/// Parses `bytes` and reports document/page metadata without rendering or
+/// uploading anything, for `?metadata_only=true` requests.
+pub fn inspect(bytes: &[u8], query: UploadQuery) -> Result<DocumentDetails, AppError> {
+    let pdfium = bind_pdfium()?;
+    let document = pdfium.load_pdf_from_byte_slice(bytes, None)?;
+
+    if let Some(scale) = query.scale
+        && !(0.1..=10.0).contains(&scale)
+    {
+        return Err(AppError::InvalidScale(
+            "scale must be between 0.1 and 10.0".to_string(),
+        ));
+    }
+
+    let blake3_id = blake3::hash(bytes).to_hex().to_string();
+
+    Ok(describe(
+        &document,
+        blake3_id,
+        query.format,
+        query.scale.unwrap_or(1.0),
+    ))
+}
+
+fn describe(document: &PdfDocument, blake3_id: String, format: OutputFormat, scale: f32) -> DocumentDetails {
+    let pages = document
+        .pages()
+        .iter()
+        .enumerate()
+        .map(|(index, page)| PageDetails {
+            index,
+            width: (page.width().value * scale).round() as u32,
+            height: (page.height().value * scale).round() as u32,
+            rotation_degrees: rotation_degrees(
+                page.rotation().unwrap_or(PdfPageRenderRotation::None),
+            ),
+        })
+        .collect();
+
+    let metadata = document.metadata();
+    let get = |tag| metadata.get(tag).map(|entry| entry.value().to_owned());
+
+    DocumentDetails {
+        blake3_id,
+        format: format.extension(),
+        mime_type: format.mime_type(),
+        page_count: document.pages().len() as usize,
+        pages,
+        title: get(PdfDocumentMetadataTagType::Title),
+        author: get(PdfDocumentMetadataTagType::Author),
+        created_at: get(PdfDocumentMetadataTagType::CreationDate),
+        modified_at: get(PdfDocumentMetadataTagType::ModificationDate),
+    }
+}
+
+fn rotation_degrees(rotation: PdfPageRenderRotation) -> u32 {
+    match rotation {
+        PdfPageRenderRotation::None => 0,
+        PdfPageRenderRotation::Degrees90 => 90,
+        PdfPageRenderRotation::Degrees180 => 180,
+        PdfPageRenderRotation::Degrees270 => 270,
+    }
+}
@@ -1,10 +1,14 @@
 use aws_sdk_s3::{
-    self as s3, error::SdkError, operation::put_object::PutObjectError, primitives::ByteStream,
+    self as s3,
+    error::SdkError,
+    operation::{get_object::GetObjectError, head_object::HeadObjectError, put_object::PutObjectError},
+    primitives::ByteStream,
 };
 use axum::{
     Json, Router,
-    extract::{DefaultBodyLimit, Multipart, Query, State, multipart::MultipartError},
-    http::StatusCode,
+    body::Body,
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State, multipart::MultipartError},
+    http::{HeaderMap, StatusCode, header},
     response::{IntoResponse, Response},
     routing::{get, post},
 };
@@ -14,13 +18,26 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::io::Cursor;
 use thiserror::Error;
-use tokio::task::{JoinError, JoinSet};
+use tokio::task::JoinError;
 use tower_http::limit::RequestBodyLimitLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod access_log;
+mod blurhash;
+mod dedup;
+mod details;
+mod processor;
+mod queue;
+mod range;
+
+use access_log::AccessLog;
+use dedup::{ConcurrentProcessor, Fingerprint};
+use queue::Queue;
+use range::ByteRange;
+
 type BoxStr = Box<str>;
 
-#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
 #[serde(rename_all = "lowercase")]
 enum OutputFormat {
     #[default]
@@ -80,6 +97,25 @@ impl OutputFormat {
             OutputFormat::Qoi => "qoi",
         }
     }
+
+    fn mime_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "image/png",
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Gif => "image/gif",
+            OutputFormat::WebP => "image/webp",
+            OutputFormat::Pnm => "image/x-portable-anymap",
+            OutputFormat::Tiff => "image/tiff",
+            OutputFormat::Tga => "image/x-tga",
+            OutputFormat::Bmp => "image/bmp",
+            OutputFormat::Ico => "image/x-icon",
+            OutputFormat::Hdr => "image/vnd.radiance",
+            OutputFormat::OpenExr => "image/x-exr",
+            OutputFormat::Farbfeld => "image/x-farbfeld",
+            OutputFormat::Avif => "image/avif",
+            OutputFormat::Qoi => "image/x-qoi",
+        }
+    }
 }
 
 struct PageSelection(HashSet<usize>);
@@ -166,6 +202,14 @@ impl std::str::FromStr for PageSelection {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum UploadMode {
+    #[default]
+    Async,
+    Sync,
+}
+
 #[derive(Debug, Deserialize)]
 struct UploadQuery {
     #[serde(default)]
@@ -173,6 +217,15 @@ struct UploadQuery {
     token: Option<String>,
     pages: Option<String>,
     scale: Option<f32>,
+    #[serde(default)]
+    mode: UploadMode,
+    #[serde(default)]
+    blurhash: bool,
+    resize: Option<String>,
+    thumbnail: Option<u32>,
+    crop: Option<String>,
+    #[serde(default)]
+    metadata_only: bool,
 }
 
 #[derive(Debug, Environment)]
@@ -192,12 +245,40 @@ struct Env {
     token: Option<String>,
     /// The address the server will listen on.
     address: Option<String>,
+    /// Path to a file to append structured per-request access log lines to.
+    /// When unset, access logging falls back to the existing tracing output.
+    access_log_path: Option<String>,
 }
 
 #[derive(Clone)]
 struct AppState {
     storage: ObjectStorage,
     token: Option<BoxStr>,
+    queue: Queue,
+    processor: ConcurrentProcessor,
+    access_log: AccessLog,
+}
+
+impl AppState {
+    /// Checks `provided` against the configured `PDF_TOKEN`, if any. Every
+    /// route that reads or reveals PDF content or derived data — uploads,
+    /// job status, and rendered images — must go through this, since a
+    /// configured token is meant to gate the whole API, not just uploads.
+    fn authorize(&self, provided: Option<&str>) -> Result<(), AppError> {
+        if let Some(expected) = &self.token {
+            match provided {
+                Some(provided) if provided == expected.as_ref() => {}
+                _ => return Err(AppError::Unauthorized),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
 }
 
 #[derive(Clone)]
@@ -230,7 +311,7 @@ impl ObjectStorage {
         }
     }
 
-    pub async fn put_image(&self, image: PdfImage) -> Result<String, AppError> {
+    pub async fn put_image(&self, image: PdfImage) -> Result<UploadedImage, AppError> {
         self.client
             .put_object()
             .bucket(self.bucket.as_ref())
@@ -240,16 +321,62 @@ impl ObjectStorage {
             .await
             .map_err(Box::new)?;
 
-        Ok(image.name)
+        Ok(UploadedImage {
+            name: image.name,
+            blurhash: image.blurhash,
+        })
+    }
+
+    pub async fn head_image(&self, key: &str) -> Result<u64, AppError> {
+        let head = self
+            .client
+            .head_object()
+            .bucket(self.bucket.as_ref())
+            .key(key)
+            .send()
+            .await
+            .map_err(Box::new)?;
+
+        Ok(head.content_length.unwrap_or(0).max(0) as u64)
+    }
+
+    pub async fn get_image(
+        &self,
+        key: &str,
+        range: Option<ByteRange>,
+    ) -> Result<ByteStream, AppError> {
+        let object = self
+            .client
+            .get_object()
+            .bucket(self.bucket.as_ref())
+            .key(key)
+            .set_range(range.map(ByteRange::to_s3_range))
+            .send()
+            .await
+            .map_err(Box::new)?;
+
+        Ok(object.body)
     }
 }
 
 struct PdfImage {
     name: String,
     stream: ByteStream,
+    blurhash: Option<String>,
 }
 
-fn process_pdf(bytes: &[u8], query: UploadQuery) -> Result<Vec<PdfImage>, AppError> {
+#[derive(Debug, Clone, Serialize)]
+struct UploadedImage {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blurhash: Option<String>,
+}
+
+/// Resolves the Pdfium library bindings: the explicit `PDFIUM_DYNAMIC_LIB_PATH`
+/// env var when it is set (a failure to bind there is returned, with no
+/// fallback); otherwise the current directory, falling back to the system
+/// library.
+fn bind_pdfium() -> Result<Pdfium, AppError> {
     let env_bindings = std::env::var("PDFIUM_DYNAMIC_LIB_PATH")
         .map(|path| {
             let path = Pdfium::pdfium_platform_library_name_at_path(&path);
@@ -264,7 +391,11 @@ fn process_pdf(bytes: &[u8], query: UploadQuery) -> Result<Vec<PdfImage>, AppErr
 
     let bindings = env_bindings.unwrap_or(current_dir_bindings.or(system_bindings))?;
 
-    let pdfium = Pdfium::new(bindings);
+    Ok(Pdfium::new(bindings))
+}
+
+fn process_pdf(bytes: &[u8], query: UploadQuery) -> Result<Vec<PdfImage>, AppError> {
+    let pdfium = bind_pdfium()?;
     let document = pdfium.load_pdf_from_byte_slice(bytes, None)?;
 
     let total_pages = document.pages().len() as usize;
@@ -289,6 +420,14 @@ fn process_pdf(bytes: &[u8], query: UploadQuery) -> Result<Vec<PdfImage>, AppErr
     let id = blake3::hash(bytes).to_hex().to_string();
     let ext = query.format.extension();
     let image_format = query.format.as_image_format();
+    let want_blurhash = query.blurhash;
+
+    let ops = processor::Operation::parse_all(
+        query.resize.as_deref(),
+        query.thumbnail,
+        query.crop.as_deref(),
+    )?;
+    let op_suffix = processor::chain_hash(&ops).map(|hash| format!("-{hash}")).unwrap_or_default();
 
     let images = document
         .pages()
@@ -300,23 +439,24 @@ fn process_pdf(bytes: &[u8], query: UploadQuery) -> Result<Vec<PdfImage>, AppErr
                 .map(|ps| ps.contains(*idx))
                 .unwrap_or(true)
         })
-        .flat_map(|(idx, page)| {
+        .map(|(idx, page)| {
             let mut output = Cursor::new(Vec::new());
+            let image = page.render_with_config(&render_config)?.as_image();
+            let image = processor::apply_all(image, &ops)?;
 
-            page.render_with_config(&render_config)
-                .ok()?
-                .as_image()
-                .write_to(&mut output, image_format)
-                .ok()?;
+            let blurhash = want_blurhash.then(|| blurhash::encode(&image, 4, 3));
+
+            image.write_to(&mut output, image_format)?;
 
             let stream = ByteStream::from(output.into_inner());
 
-            Some(PdfImage {
-                name: format!("{id}-{idx}.{ext}"),
+            Ok(PdfImage {
+                name: format!("{id}-{idx}{op_suffix}.{ext}"),
                 stream,
+                blurhash,
             })
         })
-        .collect::<Vec<_>>();
+        .collect::<Result<Vec<_>, AppError>>()?;
 
     Ok(images)
 }
@@ -325,9 +465,13 @@ fn process_pdf(bytes: &[u8], query: UploadQuery) -> Result<Vec<PdfImage>, AppErr
 async fn main() -> anyhow::Result<()> {
     let env = Env::parse();
     let storage = ObjectStorage::new(&env).await;
+    let access_log = AccessLog::new(env.access_log_path.as_deref())?;
     let state = AppState {
         storage,
         token: env.token.map(|t| t.into_boxed_str()),
+        queue: Queue::new(access_log.clone()),
+        processor: ConcurrentProcessor::new(),
+        access_log,
     };
 
     tracing_subscriber::registry()
@@ -344,18 +488,27 @@ async fn main() -> anyhow::Result<()> {
     let app = Router::new()
         .route("/health", get(health_check))
         .route("/", post(handle_pdf_upload))
+        .route("/jobs/{id}", get(get_job_status))
+        .route("/images/{key}", get(get_image))
         .layer(DefaultBodyLimit::disable())
         .layer(RequestBodyLimitLayer::new(body_limit))
         .layer(tower_http::trace::TraceLayer::new_for_http())
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            access_log::middleware,
+        ))
         .with_state(state);
 
     let address = env.address.as_deref().unwrap_or("127.0.0.1:3000");
     let listener = tokio::net::TcpListener::bind(address).await?;
 
     tracing::debug!("listening on {address}");
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await?;
 
     Ok(())
 }
@@ -371,14 +524,8 @@ async fn handle_pdf_upload(
     State(state): State<AppState>,
     Query(query): Query<UploadQuery>,
     mut multipart: Multipart,
-) -> Result<Json<UploadResponse>, AppError> {
-    // Validate token if one is configured
-    if let Some(expected_token) = &state.token {
-        match &query.token {
-            Some(provided_token) if provided_token.as_str() == expected_token.as_ref() => {}
-            _ => return Err(AppError::Unauthorized),
-        }
-    }
+) -> Result<Response, AppError> {
+    state.authorize(query.token.as_deref())?;
 
     let field = multipart
         .next_field()
@@ -386,31 +533,156 @@ async fn handle_pdf_upload(
         .ok_or_else(|| AppError::FieldNotFound)?;
 
     let data = field.bytes().await?;
-    let images = tokio::task::spawn_blocking(move || process_pdf(&data, query)).await??;
 
-    let mut set = JoinSet::new();
+    // `?metadata_only=true` inspects the document without rendering or
+    // uploading any pages.
+    if query.metadata_only {
+        let pdf_bytes = data.len() as u64;
+        let details =
+            tokio::task::spawn_blocking(move || details::inspect(&data, query)).await??;
+
+        let blake3_id = details.blake3_id.clone();
+        let mut response = Json(details).into_response();
+
+        access_log::attach(
+            &mut response,
+            access_log::RenderFields {
+                pdf_bytes,
+                pages_rendered: None,
+                blake3_id,
+            },
+        );
+
+        return Ok(response);
+    }
 
-    for image in images {
-        let storage = state.storage.clone();
-        set.spawn(async move { storage.put_image(image).await });
+    // The fingerprint must be known before rendering so concurrent identical
+    // uploads can be coalesced, so hash the bytes here, before rendering
+    // starts (`process_pdf` hashes them again to name its objects).
+    let id = blake3::hash(&data).to_hex().to_string();
+    let fingerprint = Fingerprint::new(&id, &query);
+
+    let pdf_bytes = data.len() as u64;
+
+    // `?mode=sync` keeps the original behavior of rendering and uploading
+    // before responding, for callers that can't yet poll `GET /jobs/{id}`.
+    if query.mode == UploadMode::Sync {
+        let images = state
+            .processor
+            .dedup(
+                fingerprint,
+                queue::render_and_upload(data.to_vec(), query, state.storage.clone()),
+            )
+            .await?;
+
+        let pages_rendered = images.len();
+        let mut response = Json(UploadResponse {
+            success: true,
+            images,
+        })
+        .into_response();
+
+        access_log::attach(
+            &mut response,
+            access_log::RenderFields {
+                pdf_bytes,
+                pages_rendered: Some(pages_rendered),
+                blake3_id: id,
+            },
+        );
+
+        return Ok(response);
     }
 
-    let images = set
-        .join_all()
-        .await
-        .into_iter()
-        .collect::<Result<Vec<_>, _>>()?;
+    let job_id = state
+        .queue
+        .submit(
+            fingerprint,
+            id.clone(),
+            data.to_vec(),
+            query,
+            state.storage.clone(),
+            state.processor.clone(),
+        )
+        .await;
+
+    let mut response = (StatusCode::ACCEPTED, Json(EnqueuedResponse { job_id })).into_response();
+
+    access_log::attach(
+        &mut response,
+        access_log::RenderFields {
+            pdf_bytes,
+            pages_rendered: None,
+            blake3_id: id,
+        },
+    );
+
+    Ok(response)
+}
+
+async fn get_job_status(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<TokenQuery>,
+) -> Result<Json<queue::JobState>, AppError> {
+    state.authorize(query.token.as_deref())?;
+
+    state.queue.status(&id).map(Json).ok_or(AppError::JobNotFound)
+}
+
+async fn get_image(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    Query(query): Query<TokenQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    state.authorize(query.token.as_deref())?;
+
+    let total_len = state.storage.head_image(&key).await?;
+
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .map(|header| ByteRange::parse(header, total_len))
+        .transpose()?;
+
+    let Some(range) = range else {
+        let body = Body::from_stream(state.storage.get_image(&key, None).await?);
+
+        return Ok((StatusCode::OK, [(header::ACCEPT_RANGES, "bytes")], body).into_response());
+    };
+
+    if !range.is_satisfiable(total_len) {
+        return Ok((
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            [(header::CONTENT_RANGE, format!("bytes */{total_len}"))],
+        )
+            .into_response());
+    }
 
-    Ok(Json(UploadResponse {
-        success: true,
-        images,
-    }))
+    let body = Body::from_stream(state.storage.get_image(&key, Some(range)).await?);
+
+    Ok((
+        StatusCode::PARTIAL_CONTENT,
+        [
+            (header::CONTENT_RANGE, format!("bytes {}-{}/{total_len}", range.start, range.end)),
+            (header::CONTENT_LENGTH, range.len().to_string()),
+            (header::ACCEPT_RANGES, "bytes".to_owned()),
+        ],
+        body,
+    )
+        .into_response())
 }
 
 #[derive(Debug, Serialize)]
 struct UploadResponse {
     success: bool,
-    images: Vec<String>,
+    images: Vec<UploadedImage>,
+}
+
+#[derive(Debug, Serialize)]
+struct EnqueuedResponse {
+    job_id: String,
 }
 
 #[derive(Serialize)]
@@ -434,12 +706,26 @@ enum AppError {
     Task(#[from] JoinError),
     #[error("s3 error: {0}")]
     S3(#[from] Box<SdkError<PutObjectError>>),
+    #[error("s3 error: {0}")]
+    S3Get(#[from] Box<SdkError<GetObjectError>>),
+    #[error("s3 error: {0}")]
+    S3Head(#[from] Box<SdkError<HeadObjectError>>),
+    #[error("invalid range: {0}")]
+    InvalidRange(String),
+    #[error("image encode error: {0}")]
+    ImageEncode(#[from] image::ImageError),
+    #[error("invalid operation: {0}")]
+    InvalidOperation(String),
     #[error("unauthorized: invalid or missing token")]
     Unauthorized,
     #[error("invalid page range: {0}")]
     InvalidPageRange(String),
     #[error("invalid scale: {0}")]
     InvalidScale(String),
+    #[error("no job found with the given id")]
+    JobNotFound,
+    #[error("{0}")]
+    Render(String),
 }
 #[derive(Serialize)]
 struct ErrorResponse {
@@ -471,6 +757,20 @@ impl IntoResponse for AppError {
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Internal Server Error".to_owned(),
             ),
+            AppError::S3Get(ref err) if err.as_service_error().is_some_and(GetObjectError::is_no_such_key) => {
+                (StatusCode::NOT_FOUND, "No such image".to_owned())
+            }
+            AppError::S3Get(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal Server Error".to_owned(),
+            ),
+            AppError::S3Head(ref err) if err.as_service_error().is_some_and(HeadObjectError::is_not_found) => {
+                (StatusCode::NOT_FOUND, "No such image".to_owned())
+            }
+            AppError::S3Head(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal Server Error".to_owned(),
+            ),
             AppError::Unauthorized => (
                 StatusCode::UNAUTHORIZED,
                 "Invalid or missing token".to_owned(),
@@ -482,6 +782,21 @@ impl IntoResponse for AppError {
             AppError::InvalidScale(ref msg) => {
                 (StatusCode::BAD_REQUEST, format!("Invalid scale: {}", msg))
             }
+            AppError::JobNotFound => (StatusCode::NOT_FOUND, "No such job".to_owned()),
+            AppError::Render(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal Server Error".to_owned(),
+            ),
+            AppError::InvalidRange(ref msg) => {
+                (StatusCode::BAD_REQUEST, format!("Invalid range: {}", msg))
+            }
+            AppError::ImageEncode(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal Server Error".to_owned(),
+            ),
+            AppError::InvalidOperation(ref msg) => {
+                (StatusCode::BAD_REQUEST, format!("Invalid operation: {}", msg))
+            }
         };
 
         (status, Json(ErrorResponse { message })).into_response()
@@ -0,0 +1,215 @@
+use image::DynamicImage;
+
+use crate::AppError;
+
+/// A single page-image transform, applied in a fixed resize -> thumbnail ->
+/// crop order so a request's operation chain is always reproducible.
+#[derive(Debug, Clone, Copy)]
+pub enum Operation {
+    Resize { width: u32, height: u32 },
+    Thumbnail { size: u32 },
+    Crop { x: u32, y: u32, width: u32, height: u32 },
+}
+
+impl Operation {
+    /// Parses the `resize`, `thumbnail`, and `crop` query parameters into an
+    /// ordered operation chain.
+    pub fn parse_all(
+        resize: Option<&str>,
+        thumbnail: Option<u32>,
+        crop: Option<&str>,
+    ) -> Result<Vec<Self>, AppError> {
+        let mut ops = Vec::new();
+
+        if let Some(resize) = resize {
+            ops.push(Self::parse_resize(resize)?);
+        }
+
+        if let Some(size) = thumbnail {
+            if size == 0 {
+                return Err(AppError::InvalidOperation(
+                    "thumbnail size must be greater than 0".to_string(),
+                ));
+            }
+            ops.push(Operation::Thumbnail { size });
+        }
+
+        if let Some(crop) = crop {
+            ops.push(Self::parse_crop(crop)?);
+        }
+
+        Ok(ops)
+    }
+
+    fn parse_resize(spec: &str) -> Result<Self, AppError> {
+        let invalid = || AppError::InvalidOperation(format!("invalid resize spec: {spec}"));
+
+        let (width, height) = spec.split_once('x').ok_or_else(invalid)?;
+        let width: u32 = width.parse().map_err(|_| invalid())?;
+        let height: u32 = height.parse().map_err(|_| invalid())?;
+
+        if width == 0 || height == 0 {
+            return Err(AppError::InvalidOperation(
+                "resize dimensions must be greater than 0".to_string(),
+            ));
+        }
+
+        Ok(Operation::Resize { width, height })
+    }
+
+    fn parse_crop(spec: &str) -> Result<Self, AppError> {
+        let invalid = || AppError::InvalidOperation(format!("invalid crop spec: {spec}"));
+
+        let parts: Vec<&str> = spec.split(',').collect();
+        let [x, y, width, height] = parts.as_slice() else {
+            return Err(invalid());
+        };
+
+        let parse = |s: &str| s.trim().parse::<u32>().map_err(|_| invalid());
+
+        Ok(Operation::Crop {
+            x: parse(x)?,
+            y: parse(y)?,
+            width: parse(width)?,
+            height: parse(height)?,
+        })
+    }
+
+    fn apply(&self, image: DynamicImage) -> Result<DynamicImage, AppError> {
+        match *self {
+            Operation::Resize { width, height } => Ok(image.resize_exact(
+                width,
+                height,
+                image::imageops::FilterType::Lanczos3,
+            )),
+            Operation::Thumbnail { size } => Ok(image.thumbnail(size, size)),
+            Operation::Crop { x, y, width, height } => {
+                if x.saturating_add(width) > image.width() || y.saturating_add(height) > image.height() {
+                    return Err(AppError::InvalidOperation(format!(
+                        "crop region ({x},{y},{width}x{height}) is out of bounds for a {}x{} image",
+                        image.width(),
+                        image.height()
+                    )));
+                }
+
+                Ok(image.crop_imm(x, y, width, height))
+            }
+        }
+    }
+}
+
+/// Applies every operation in `ops` to `image`, in order.
+pub fn apply_all(mut image: DynamicImage, ops: &[Operation]) -> Result<DynamicImage, AppError> {
+    for op in ops {
+        image = op.apply(image)?;
+    }
+
+    Ok(image)
+}
+
+/// A short, stable hash of an operation chain, used so distinct transform
+/// chains of the same source PDF land on distinct, cacheable object keys.
+pub fn chain_hash(ops: &[Operation]) -> Option<String> {
+    if ops.is_empty() {
+        return None;
+    }
+
+    let mut hasher = blake3::Hasher::new();
+    for op in ops {
+        hasher.update(format!("{op:?}").as_bytes());
+    }
+
+    Some(hasher.finalize().to_hex().as_str()[..8].to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    fn image(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::new(width, height))
+    }
+
+    #[test]
+    fn no_params_parses_to_an_empty_chain() {
+        let ops = Operation::parse_all(None, None, None).unwrap();
+        assert!(ops.is_empty());
+    }
+
+    #[test]
+    fn parses_a_resize_spec() {
+        let ops = Operation::parse_all(Some("800x600"), None, None).unwrap();
+        assert!(matches!(
+            ops.as_slice(),
+            [Operation::Resize { width: 800, height: 600 }]
+        ));
+    }
+
+    #[test]
+    fn rejects_a_resize_spec_missing_the_separator() {
+        assert!(Operation::parse_all(Some("800"), None, None).is_err());
+    }
+
+    #[test]
+    fn rejects_a_zero_dimension_resize() {
+        assert!(Operation::parse_all(Some("0x600"), None, None).is_err());
+    }
+
+    #[test]
+    fn rejects_a_zero_thumbnail_size() {
+        assert!(Operation::parse_all(None, Some(0), None).is_err());
+    }
+
+    #[test]
+    fn parses_a_crop_spec() {
+        let ops = Operation::parse_all(None, None, Some("1,2,3,4")).unwrap();
+        assert!(matches!(
+            ops.as_slice(),
+            [Operation::Crop { x: 1, y: 2, width: 3, height: 4 }]
+        ));
+    }
+
+    #[test]
+    fn rejects_a_crop_spec_with_the_wrong_arity() {
+        assert!(Operation::parse_all(None, None, Some("1,2,3")).is_err());
+    }
+
+    #[test]
+    fn chain_is_always_resize_then_thumbnail_then_crop() {
+        let ops = Operation::parse_all(Some("100x100"), Some(50), Some("0,0,10,10")).unwrap();
+        assert!(matches!(
+            ops.as_slice(),
+            [
+                Operation::Resize { .. },
+                Operation::Thumbnail { .. },
+                Operation::Crop { .. },
+            ]
+        ));
+    }
+
+    #[test]
+    fn crop_out_of_bounds_is_rejected_at_apply_time() {
+        let ops = vec![Operation::Crop { x: 0, y: 0, width: 200, height: 200 }];
+        assert!(apply_all(image(100, 100), &ops).is_err());
+    }
+
+    #[test]
+    fn crop_within_bounds_succeeds() {
+        let ops = vec![Operation::Crop { x: 10, y: 10, width: 50, height: 50 }];
+        let result = apply_all(image(100, 100), &ops).unwrap();
+        assert_eq!((result.width(), result.height()), (50, 50));
+    }
+
+    #[test]
+    fn empty_chain_has_no_hash() {
+        assert!(chain_hash(&[]).is_none());
+    }
+
+    #[test]
+    fn distinct_chains_hash_differently() {
+        let a = [Operation::Resize { width: 100, height: 100 }];
+        let b = [Operation::Resize { width: 200, height: 200 }];
+        assert_ne!(chain_hash(&a), chain_hash(&b));
+    }
+}
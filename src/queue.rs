@@ -0,0 +1,297 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use serde::Serialize;
+use tokio::{
+    sync::{mpsc, Semaphore},
+    task::JoinSet,
+};
+use uuid::Uuid;
+
+use crate::{
+    access_log::AccessLog,
+    dedup::{ConcurrentProcessor, Fingerprint},
+    process_pdf, AppError, ObjectStorage, UploadQuery, UploadedImage,
+};
+
+/// How long a finished job's state is kept around for polling before the reaper evicts it.
+const JOB_TTL: Duration = Duration::from_secs(5 * 60);
+/// Maximum number of render-and-upload jobs running at once.
+const WORKER_COUNT: usize = 4;
+
+pub type JobId = String;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Completed { images: Vec<UploadedImage> },
+    Failed { error: String },
+}
+
+struct JobEntry {
+    state: JobState,
+    finished_at: Option<Instant>,
+}
+
+struct JobRequest {
+    id: JobId,
+    /// The content hash of the uploaded bytes, logged alongside the job's
+    /// completion so it can be cross-referenced with the upload's own access
+    /// log line. Distinct from `id`: the job id is per-submission, while this
+    /// is shared by every submission of the same bytes/params.
+    blake3_id: String,
+    fingerprint: Fingerprint,
+    bytes: Vec<u8>,
+    query: UploadQuery,
+    storage: ObjectStorage,
+    processor: ConcurrentProcessor,
+}
+
+/// Shared handle to the background render-and-upload queue.
+///
+/// Cloning a `Queue` is cheap: the job map and the channel sender are both
+/// reference-counted.
+#[derive(Clone)]
+pub struct Queue {
+    jobs: Arc<DashMap<JobId, JobEntry>>,
+    tx: mpsc::Sender<JobRequest>,
+}
+
+impl Queue {
+    pub fn new(access_log: AccessLog) -> Self {
+        let jobs: Arc<DashMap<JobId, JobEntry>> = Arc::new(DashMap::new());
+        let (tx, rx) = mpsc::channel(256);
+
+        spawn_dispatcher(jobs.clone(), rx, access_log);
+        spawn_reaper(jobs.clone());
+
+        Self { jobs, tx }
+    }
+
+    /// Enqueues a job and returns the freshly generated id it was filed
+    /// under.
+    ///
+    /// The id is a UUID, not `blake3_id` (the content hash carried in
+    /// `fingerprint`): two submissions of byte-identical content — a
+    /// duplicate browser submit, a client retry, two users uploading the same
+    /// template — must land on distinct `jobs` entries, or the second
+    /// submission's `Queued`/`Running` writes would stomp the first's
+    /// terminal state. `ConcurrentProcessor` still dedupes the underlying
+    /// render by `fingerprint`, so identical content is only rendered once.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn submit(
+        &self,
+        fingerprint: Fingerprint,
+        blake3_id: String,
+        bytes: Vec<u8>,
+        query: UploadQuery,
+        storage: ObjectStorage,
+        processor: ConcurrentProcessor,
+    ) -> JobId {
+        let id = Uuid::new_v4().to_string();
+
+        self.jobs
+            .insert(id.clone(), JobEntry { state: JobState::Queued, finished_at: None });
+
+        // `send` waits while the channel is full and only fails once the
+        // dispatcher's receiver is gone; in that case there's nothing better
+        // to do than drop the job, so the send error is ignored and the job's
+        // entry stays `Queued`.
+        let _ = self
+            .tx
+            .send(JobRequest {
+                id: id.clone(),
+                blake3_id,
+                fingerprint,
+                bytes,
+                query,
+                storage,
+                processor,
+            })
+            .await;
+
+        id
+    }
+
+    pub fn status(&self, id: &str) -> Option<JobState> {
+        self.jobs.get(id).map(|entry| entry.state.clone())
+    }
+}
+
+fn spawn_dispatcher(
+    jobs: Arc<DashMap<JobId, JobEntry>>,
+    mut rx: mpsc::Receiver<JobRequest>,
+    access_log: AccessLog,
+) {
+    tokio::spawn(async move {
+        let semaphore = Arc::new(Semaphore::new(WORKER_COUNT));
+
+        // Jobs are spawned directly rather than tracked in a `JoinSet`: the
+        // semaphore already caps how many run at once, and `tx` lives in
+        // `AppState` for the process lifetime, so a `JoinSet` here would just
+        // accumulate one finished-but-unreaped entry per job forever.
+        while let Some(job) = rx.recv().await {
+            let permit = semaphore
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("semaphore is never closed");
+            let jobs = jobs.clone();
+            let access_log = access_log.clone();
+
+            tokio::spawn(async move {
+                run_job(&jobs, &access_log, job).await;
+                drop(permit);
+            });
+        }
+    });
+}
+
+async fn run_job(jobs: &DashMap<JobId, JobEntry>, access_log: &AccessLog, job: JobRequest) {
+    jobs.insert(job.id.clone(), JobEntry { state: JobState::Running, finished_at: None });
+
+    let started = Instant::now();
+    let result = job
+        .processor
+        .dedup(job.fingerprint, render_and_upload(job.bytes, job.query, job.storage))
+        .await;
+    let duration = started.elapsed();
+
+    let (state, pages_rendered, error) = match result {
+        Ok(images) => {
+            let pages_rendered = images.len();
+            (JobState::Completed { images }, Some(pages_rendered), None)
+        }
+        Err(err) => {
+            let message = err.to_string();
+            (JobState::Failed { error: message.clone() }, None, Some(message))
+        }
+    };
+
+    access_log.record_job_completion(&job.id, &job.blake3_id, pages_rendered, error.as_deref(), duration);
+
+    jobs.insert(job.id, JobEntry { state, finished_at: Some(Instant::now()) });
+}
+
+/// Renders every selected page and uploads the results, without any
+/// deduplication of its own — callers route through [`ConcurrentProcessor`]
+/// so identical in-flight requests share one render.
+pub(crate) async fn render_and_upload(
+    bytes: Vec<u8>,
+    query: UploadQuery,
+    storage: ObjectStorage,
+) -> Result<Vec<UploadedImage>, AppError> {
+    let images = tokio::task::spawn_blocking(move || process_pdf(&bytes, query)).await??;
+
+    let mut set = JoinSet::new();
+    for image in images {
+        let storage = storage.clone();
+        set.spawn(async move { storage.put_image(image).await });
+    }
+
+    set.join_all().await.into_iter().collect()
+}
+
+fn spawn_reaper(jobs: Arc<DashMap<JobId, JobEntry>>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+
+        loop {
+            interval.tick().await;
+            jobs.retain(|_, entry| entry.finished_at.is_none_or(|t| t.elapsed() < JOB_TTL));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OutputFormat;
+
+    fn dummy_query() -> UploadQuery {
+        UploadQuery {
+            format: OutputFormat::default(),
+            token: None,
+            pages: None,
+            scale: None,
+            mode: Default::default(),
+            blurhash: false,
+            resize: None,
+            thumbnail: None,
+            crop: None,
+            metadata_only: false,
+        }
+    }
+
+    /// An `ObjectStorage` that never touches the network: these tests only
+    /// exercise job bookkeeping, and garbage PDF bytes make `process_pdf`
+    /// fail before any S3 call would be made.
+    fn dummy_storage() -> ObjectStorage {
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version(aws_config::BehaviorVersion::latest())
+            .region(aws_sdk_s3::config::Region::new("auto"))
+            .credentials_provider(aws_sdk_s3::config::Credentials::new(
+                "test", "test", None, None, "test",
+            ))
+            .endpoint_url("http://localhost:0")
+            .build();
+
+        ObjectStorage {
+            bucket: "test".into(),
+            client: aws_sdk_s3::Client::from_conf(config),
+        }
+    }
+
+    async fn wait_for_terminal(queue: &Queue, id: &str) -> JobState {
+        for _ in 0..200 {
+            if let Some(state @ (JobState::Completed { .. } | JobState::Failed { .. })) = queue.status(id) {
+                return state;
+            }
+
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        panic!("job {id} did not reach a terminal state in time");
+    }
+
+    #[tokio::test]
+    async fn concurrent_submissions_of_identical_content_never_clobber_each_other() {
+        let queue = Queue::new(AccessLog::new(None).unwrap());
+        let processor = ConcurrentProcessor::new();
+        let fingerprint = Fingerprint::new("deadbeef", &dummy_query());
+
+        // Garbage bytes fail to parse as a PDF, so both jobs reach `Failed`
+        // quickly without a real render or upload.
+        let bytes = vec![0u8; 16];
+
+        let (first, second) = tokio::join!(
+            queue.submit(
+                fingerprint.clone(),
+                "deadbeef".to_owned(),
+                bytes.clone(),
+                dummy_query(),
+                dummy_storage(),
+                processor.clone(),
+            ),
+            queue.submit(
+                fingerprint,
+                "deadbeef".to_owned(),
+                bytes,
+                dummy_query(),
+                dummy_storage(),
+                processor,
+            ),
+        );
+
+        assert_ne!(first, second, "each submission must get its own job id");
+
+        for id in [&first, &second] {
+            assert!(matches!(wait_for_terminal(&queue, id).await, JobState::Failed { .. }));
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use crate::AppError;
+
+/// An inclusive byte range resolved against a known total object length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    /// Parses a single-range `Range` header (`bytes=start-end`, `bytes=start-`,
+    /// or `bytes=-suffix`) against `total_len`, clamping `end` to the end of
+    /// the object.
+    pub fn parse(header: &str, total_len: u64) -> Result<Self, AppError> {
+        let invalid = || AppError::InvalidRange(header.to_owned());
+
+        let spec = header.strip_prefix("bytes=").ok_or_else(invalid)?;
+        let (start, end) = spec.split_once('-').ok_or_else(invalid)?;
+
+        if start.is_empty() {
+            let suffix: u64 = end.parse().map_err(|_| invalid())?;
+            let start = total_len.saturating_sub(suffix);
+            return Ok(ByteRange {
+                start,
+                end: total_len.saturating_sub(1),
+            });
+        }
+
+        let start: u64 = start.parse().map_err(|_| invalid())?;
+        let end = if end.is_empty() {
+            total_len.saturating_sub(1)
+        } else {
+            end.parse::<u64>().map_err(|_| invalid())?.min(total_len.saturating_sub(1))
+        };
+
+        Ok(ByteRange { start, end })
+    }
+
+    pub fn is_satisfiable(&self, total_len: u64) -> bool {
+        total_len > 0 && self.start <= self.end && self.start < total_len
+    }
+
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    pub fn to_s3_range(self) -> String {
+        format!("bytes={}-{}", self.start, self.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_start_and_end() {
+        let range = ByteRange::parse("bytes=0-499", 1000).unwrap();
+        assert_eq!(range, ByteRange { start: 0, end: 499 });
+    }
+
+    #[test]
+    fn parses_open_ended_start() {
+        let range = ByteRange::parse("bytes=500-", 1000).unwrap();
+        assert_eq!(range, ByteRange { start: 500, end: 999 });
+    }
+
+    #[test]
+    fn parses_suffix_range() {
+        let range = ByteRange::parse("bytes=-500", 1000).unwrap();
+        assert_eq!(range, ByteRange { start: 500, end: 999 });
+    }
+
+    #[test]
+    fn clamps_end_past_total_len() {
+        let range = ByteRange::parse("bytes=0-9999", 1000).unwrap();
+        assert_eq!(range, ByteRange { start: 0, end: 999 });
+    }
+
+    #[test]
+    fn clamps_oversized_suffix_to_whole_object() {
+        let range = ByteRange::parse("bytes=-5000", 1000).unwrap();
+        assert_eq!(range, ByteRange { start: 0, end: 999 });
+    }
+
+    #[test]
+    fn rejects_missing_bytes_prefix() {
+        assert!(ByteRange::parse("0-499", 1000).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_spec() {
+        assert!(ByteRange::parse("bytes=abc-def", 1000).is_err());
+    }
+
+    #[test]
+    fn reversed_range_is_unsatisfiable() {
+        let range = ByteRange::parse("bytes=500-100", 1000).unwrap();
+        assert!(!range.is_satisfiable(1000));
+    }
+
+    #[test]
+    fn zero_length_object_is_never_satisfiable() {
+        let range = ByteRange::parse("bytes=0-0", 0).unwrap();
+        assert!(!range.is_satisfiable(0));
+    }
+
+    #[test]
+    fn len_is_inclusive() {
+        let range = ByteRange { start: 0, end: 99 };
+        assert_eq!(range.len(), 100);
+    }
+
+    #[test]
+    fn formats_s3_range() {
+        let range = ByteRange { start: 10, end: 20 };
+        assert_eq!(range.to_s3_range(), "bytes=10-20");
+    }
+}